@@ -0,0 +1,1082 @@
+/* Copyright (C) 2020 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! BER/DER TLV decoder for the `asn1` keyword.
+//!
+//! Walks a buffer as a tree of Tag-Length-Value nodes and evaluates the
+//! conditions carried in [`DetectAsn1Data`] against what was decoded.
+
+use crate::asn1::parse_rules::DetectAsn1Data;
+use nom::bytes::streaming::take;
+use nom::number::streaming::be_u8;
+use nom::{IResult, Needed};
+
+/// Universal tag number for BIT STRING.
+const ASN1_TAG_BITSTRING: u32 = 3;
+/// Universal tag number for REAL.
+const ASN1_TAG_REAL: u32 = 9;
+/// Refuse to honor a long-form length claiming more octets than this; a
+/// real-world length never needs anywhere close to it and it keeps a
+/// malicious header from driving unbounded reads.
+const ASN1_MAX_LENGTH_OCTETS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Asn1Class {
+    Universal,
+    Application,
+    ContextSpecific,
+    Private,
+}
+
+impl Asn1Class {
+    fn from_identifier_octet(b: u8) -> Asn1Class {
+        match (b >> 6) & 0x3 {
+            0 => Asn1Class::Universal,
+            1 => Asn1Class::Application,
+            2 => Asn1Class::ContextSpecific,
+            _ => Asn1Class::Private,
+        }
+    }
+}
+
+/// One decoded TLV header, with `value` holding the (unparsed) contents.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Asn1Node<'a> {
+    pub class: Asn1Class,
+    pub tag_number: u32,
+    pub constructed: bool,
+    pub header_len: usize,
+    pub length: usize,
+    pub value: &'a [u8],
+}
+
+/// A condition from [`DetectAsn1Data`] that matched a decoded node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Asn1ConditionKind {
+    OversizeLength,
+    BitstringOverflow,
+    DoubleOverflow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asn1Condition {
+    pub kind: Asn1ConditionKind,
+    /// Offset of the matching node's identifier octet within the buffer
+    /// passed to [`asn1_decode`].
+    pub offset: usize,
+    pub tag_number: u32,
+}
+
+/// Result of running [`asn1_decode`] over a buffer.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Asn1MatchResult {
+    pub matched: bool,
+    pub conditions: Vec<Asn1Condition>,
+}
+
+impl Asn1MatchResult {
+    fn record(&mut self, kind: Asn1ConditionKind, offset: usize, tag_number: u32) {
+        self.matched = true;
+        self.conditions.push(Asn1Condition { kind, offset, tag_number });
+    }
+}
+
+/// Why [`try_parse_tlv`] could not hand back a complete node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Asn1TlvError {
+    /// The header parsed fine, or didn't parse at all yet, purely because
+    /// the buffer ran out; feeding at least `needed` more bytes and
+    /// retrying may succeed. This is the condition a streaming caller
+    /// should wait on rather than give up on.
+    Incomplete(usize),
+    /// The header itself is malformed (e.g. an indefinite-form or
+    /// implausibly large length) — more bytes will not fix this.
+    Invalid,
+}
+
+/// Parse the identifier octet(s) of a TLV: class, constructed flag and tag
+/// number, expanding the long form when the low 5 bits are all set. Built
+/// on nom's `streaming` combinators (not `complete`), which yield
+/// `Err::Incomplete` rather than `Err::Error` when the buffer simply runs
+/// out mid-field — exactly the signal [`try_parse_tlv`] needs to tell a
+/// short buffer apart from a malformed one.
+fn parse_identifier(input: &[u8]) -> IResult<&[u8], (Asn1Class, bool, u32)> {
+    let (rest, first) = be_u8(input)?;
+    let class = Asn1Class::from_identifier_octet(first);
+    let constructed = first & 0x20 != 0;
+    let low_tag_bits = first & 0x1f;
+
+    if low_tag_bits != 0x1f {
+        return Ok((rest, (class, constructed, low_tag_bits as u32)));
+    }
+
+    let mut tag_number: u32 = 0;
+    let mut rest = rest;
+    loop {
+        let (r, b) = be_u8(rest)?;
+        tag_number = (tag_number << 7) | (b & 0x7f) as u32;
+        rest = r;
+        if b & 0x80 == 0 {
+            break;
+        }
+    }
+    Ok((rest, (class, constructed, tag_number)))
+}
+
+/// Parse the length octet(s) of a TLV, short or long form.
+fn parse_length(input: &[u8]) -> IResult<&[u8], usize> {
+    let (rest, first) = be_u8(input)?;
+    if first & 0x80 == 0 {
+        return Ok((rest, (first & 0x7f) as usize));
+    }
+
+    let num_octets = (first & 0x7f) as usize;
+    if num_octets == 0 || num_octets > ASN1_MAX_LENGTH_OCTETS {
+        // Indefinite form or an implausibly large length field; neither is
+        // handled here, and no amount of extra buffering fixes it.
+        return Err(nom::Err::Error(nom::error::make_error(input, nom::error::ErrorKind::TooLarge)));
+    }
+    let (rest, octets) = take(num_octets)(rest)?;
+    let length = octets.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+    Ok((rest, length))
+}
+
+/// How many more bytes a `nom::Err::Incomplete` says are needed, falling
+/// back to 1 when nom can't size the request precisely.
+fn needed_len(needed: Needed) -> usize {
+    match needed {
+        Needed::Size(n) => n.get(),
+        Needed::Unknown => 1,
+    }
+}
+
+/// Parse a single TLV header and slice off its value bytes, distinguishing
+/// a buffer that simply needs more bytes from one that is malformed so
+/// that streaming callers know whether to wait or to give up.
+pub(crate) fn try_parse_tlv(input: &[u8]) -> Result<(&[u8], Asn1Node<'_>), Asn1TlvError> {
+    let (rest, (class, constructed, tag_number)) = match parse_identifier(input) {
+        Ok(v) => v,
+        Err(nom::Err::Incomplete(needed)) => return Err(Asn1TlvError::Incomplete(needed_len(needed))),
+        Err(_) => return Err(Asn1TlvError::Invalid),
+    };
+    let (rest, length) = match parse_length(rest) {
+        Ok(v) => v,
+        Err(nom::Err::Incomplete(needed)) => return Err(Asn1TlvError::Incomplete(needed_len(needed))),
+        Err(_) => return Err(Asn1TlvError::Invalid),
+    };
+    let header_len = input.len() - rest.len();
+    let (rest, value) = match take::<_, _, nom::error::Error<&[u8]>>(length)(rest) {
+        Ok(v) => v,
+        Err(nom::Err::Incomplete(needed)) => return Err(Asn1TlvError::Incomplete(needed_len(needed))),
+        Err(_) => return Err(Asn1TlvError::Invalid),
+    };
+
+    Ok((rest, Asn1Node { class, tag_number, constructed, header_len, length, value }))
+}
+
+/// Does this REAL value's mantissa/exponent encoding overflow a host `f64`?
+fn asn1_real_overflows_f64(value: &[u8]) -> bool {
+    if value.is_empty() {
+        // The empty encoding is the real value zero.
+        return false;
+    }
+
+    let first = value[0];
+    if first & 0x80 != 0 {
+        // Binary encoding (X.690 8.5.7).
+        let base: f64 = match (first >> 4) & 0x3 {
+            0 => 2.0,
+            1 => 8.0,
+            2 => 16.0,
+            _ => return true,
+        };
+        let scale = ((first >> 2) & 0x3) as i32;
+        let (exp_start, exp_len) = match first & 0x3 {
+            0 => (1, 1),
+            1 => (1, 2),
+            2 => (1, 3),
+            _ => {
+                if value.len() < 2 {
+                    return true;
+                }
+                (2, value[1] as usize)
+            }
+        };
+        // A legitimate exponent never needs anywhere close to this many
+        // octets; beyond it `exponent`'s accumulation below would wrap
+        // past the point where `exponent.unsigned_abs() > 1100` could catch it, so
+        // treat it as an overflow outright instead of silently truncating
+        // the high-order bytes.
+        if exp_len == 0 || exp_len > ASN1_MAX_LENGTH_OCTETS || value.len() < exp_start + exp_len {
+            return true;
+        }
+
+        let mut exponent: i64 = if value[exp_start] & 0x80 != 0 { -1 } else { 0 };
+        for &b in &value[exp_start..exp_start + exp_len] {
+            exponent = (exponent << 8) | b as i64;
+        }
+
+        let mantissa_bytes = &value[exp_start + exp_len..];
+        // `unsigned_abs`, not `abs`: an 8-octet exponent can legitimately
+        // decode to `i64::MIN`, which has no positive `i64` representation
+        // and would panic (or silently stay negative without overflow
+        // checks) if negated directly.
+        if mantissa_bytes.is_empty() || exponent.unsigned_abs() > 1100 {
+            return true;
+        }
+        let mut mantissa: i128 = 0;
+        for &b in mantissa_bytes {
+            mantissa = (mantissa << 8) | b as i128;
+        }
+        if first & 0x40 != 0 {
+            mantissa = -mantissa;
+        }
+        mantissa <<= scale;
+
+        let decoded = (mantissa as f64) * base.powi(exponent as i32);
+        !decoded.is_finite()
+    } else if first & 0x40 != 0 {
+        // Special real value: +inf, -inf, NaN or -0, all representable.
+        false
+    } else {
+        // Decimal encoding (ISO 6093 NR1/NR2/NR3). NR2/NR3 permit a comma as
+        // the decimal separator, which f64's parser doesn't understand, so
+        // normalize it to a period before parsing rather than treating it
+        // as an unrepresentable value.
+        match std::str::from_utf8(&value[1..]) {
+            Ok(s) => match s.trim().replace(',', ".").parse::<f64>() {
+                Ok(f) => !f.is_finite(),
+                Err(_) => true,
+            },
+            Err(_) => true,
+        }
+    }
+}
+
+fn evaluate_node(node: &Asn1Node, offset: usize, data: &DetectAsn1Data, result: &mut Asn1MatchResult) {
+    if let Some(max) = data.oversize_length {
+        if node.length as u64 > max as u64 {
+            result.record(Asn1ConditionKind::OversizeLength, offset, node.tag_number);
+        }
+    }
+
+    if data.bitstring_overflow
+        && node.class == Asn1Class::Universal
+        && node.tag_number == ASN1_TAG_BITSTRING
+        && !node.constructed
+    {
+        if let Some(&unused_bits) = node.value.first() {
+            if unused_bits > 7 {
+                result.record(Asn1ConditionKind::BitstringOverflow, offset, node.tag_number);
+            }
+        }
+    }
+
+    if data.double_overflow
+        && node.class == Asn1Class::Universal
+        && node.tag_number == ASN1_TAG_REAL
+        && asn1_real_overflows_f64(node.value)
+    {
+        result.record(Asn1ConditionKind::DoubleOverflow, offset, node.tag_number);
+    }
+}
+
+/// Walk `buf` (starting at absolute `offset` within the original input) as
+/// a sequence of TLV nodes, descending into constructed values via an
+/// explicit stack rather than native recursion so that a deeply nested
+/// (as opposed to merely wide) structure can't exhaust the thread stack.
+/// Stops once `frames_left` reaches zero rather than erroring, mirroring
+/// how the keyword has always bounded decode cost via `max_frames`.
+fn decode_nodes(buf: &[u8], offset: usize, data: &DetectAsn1Data, frames_left: &mut u16, result: &mut Asn1MatchResult) {
+    struct Frame<'a> {
+        buf: &'a [u8],
+        offset: usize,
+    }
+
+    let mut stack = vec![Frame { buf, offset }];
+
+    while let Some(Frame { mut buf, mut offset }) = stack.pop() {
+        while !buf.is_empty() && *frames_left > 0 {
+            *frames_left -= 1;
+
+            let node = match try_parse_tlv(buf) {
+                Ok((_, node)) => node,
+                Err(_) => break,
+            };
+
+            evaluate_node(&node, offset, data, result);
+
+            let consumed = node.header_len + node.length;
+            let next_offset = offset + consumed;
+            let rest = &buf[consumed..];
+
+            if node.constructed {
+                // Resume this sibling level once the child subtree is done.
+                stack.push(Frame { buf: rest, offset: next_offset });
+                stack.push(Frame { buf: node.value, offset: offset + node.header_len });
+                break;
+            }
+
+            buf = rest;
+            offset = next_offset;
+        }
+    }
+}
+
+/// Apply `absolute_offset`/`relative_offset` to find where decoding should
+/// start; returns `None` when the resulting offset falls outside `input`.
+fn asn1_start_offset(input: &[u8], data: &DetectAsn1Data) -> Option<usize> {
+    let mut offset: i64 = data.absolute_offset.unwrap_or(0) as i64;
+    if let Some(rel) = data.relative_offset {
+        offset += rel as i64;
+    }
+    if offset < 0 || offset as usize > input.len() {
+        return None;
+    }
+    Some(offset as usize)
+}
+
+/// Decode `input` as a BER/DER structure and check it against the
+/// conditions carried in `data`, bounding recursion/iteration by
+/// `data.max_frames`.
+pub fn asn1_decode(input: &[u8], data: &DetectAsn1Data) -> Asn1MatchResult {
+    let mut result = Asn1MatchResult::default();
+
+    let offset = match asn1_start_offset(input, data) {
+        Some(offset) => offset,
+        None => return result,
+    };
+
+    let mut frames_left = data.max_frames;
+    decode_nodes(&input[offset..], offset, data, &mut frames_left, &mut result);
+
+    result
+}
+
+/// `asn1_decode`, callable from the C detection engine on a buffered
+/// payload.
+///
+/// # Safety
+///
+/// `data` must point to a valid `DetectAsn1Data`; `input` must be valid
+/// for `input_len` bytes (or `input_len` zero).
+#[no_mangle]
+pub unsafe extern "C" fn rs_detect_asn1_decode(
+    data: *const DetectAsn1Data, input: *const u8, input_len: u32,
+) -> u8 {
+    if data.is_null() || (input.is_null() && input_len != 0) {
+        return 0;
+    }
+    let data = &*data;
+    let buf = if input_len == 0 { &[] } else { std::slice::from_raw_parts(input, input_len as usize) };
+
+    asn1_decode(buf, data).matched as u8
+}
+
+/// How an incremental decode of a chunked payload currently stands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Asn1DecodeStatus {
+    /// Decoding has stopped: every buffered top-level TLV was walked,
+    /// `max_frames` was exhausted, or a malformed node was hit. In the last
+    /// two cases feeding more data to the same state will not change
+    /// `result` further; in the first, a later `feed` goes on to decode
+    /// whatever top-level TLVs follow.
+    Done(Asn1MatchResult),
+    /// The TLV currently being decoded needs at least `needed` more bytes
+    /// than are buffered so far.
+    Incomplete { needed: usize },
+    /// Honoring `needed` more bytes would exceed the state's configured
+    /// buffering cap; the decode is abandoned.
+    TooLarge,
+}
+
+/// One level of ASN.1 nesting an in-progress incremental decode is inside;
+/// `remaining` counts down the declared length of that constructed node's
+/// value as child TLVs are consumed. `None` at the synthetic top-level
+/// frame, which is not itself bounded by a declared length.
+#[derive(Debug, Clone, Copy)]
+struct Asn1Frame {
+    remaining: Option<usize>,
+}
+
+/// Incremental decode state for an asn1 structure that may be split across
+/// multiple TCP segments. Only ever buffers the bytes of the TLV currently
+/// being completed plus whatever constructed ancestors are still open;
+/// already-decoded siblings are dropped as soon as they are consumed.
+pub struct Asn1DecodeState {
+    data: DetectAsn1Data,
+    buffer: Vec<u8>,
+    stack: Vec<Asn1Frame>,
+    total_consumed: usize,
+    /// Bytes of `data.absolute_offset`/`relative_offset` still to be
+    /// skipped before decoding starts; drained from `buffer` as it fills
+    /// up, same as [`asn1_start_offset`] does up front for the one-shot
+    /// `asn1_decode`.
+    skip: usize,
+    frames_left: u16,
+    max_buffered_bytes: usize,
+    result: Asn1MatchResult,
+}
+
+impl Asn1DecodeState {
+    /// `max_buffered_bytes` of 0 means no cap beyond `data.max_frames`.
+    pub fn new(data: &DetectAsn1Data, max_buffered_bytes: usize) -> Self {
+        let mut offset: i64 = data.absolute_offset.unwrap_or(0) as i64;
+        if let Some(rel) = data.relative_offset {
+            offset += rel as i64;
+        }
+        // A negative start offset never decodes anything, same as
+        // `asn1_start_offset` returning `None`; stop immediately rather
+        // than waiting on bytes that would never be reached anyway.
+        let (skip, frames_left) = if offset < 0 { (0, 0) } else { (offset as usize, data.max_frames) };
+
+        Asn1DecodeState {
+            data: data.clone(),
+            buffer: Vec::new(),
+            stack: vec![Asn1Frame { remaining: None }],
+            total_consumed: skip,
+            skip,
+            frames_left,
+            max_buffered_bytes,
+            result: Asn1MatchResult::default(),
+        }
+    }
+
+    /// Feed the next chunk of the payload and advance decoding as far as
+    /// the buffered bytes allow.
+    pub fn feed(&mut self, chunk: &[u8]) -> Asn1DecodeStatus {
+        self.buffer.extend_from_slice(chunk);
+
+        if self.skip > 0 {
+            let drop_now = self.skip.min(self.buffer.len());
+            self.buffer.drain(..drop_now);
+            self.skip -= drop_now;
+            if self.skip > 0 {
+                return Asn1DecodeStatus::Incomplete { needed: self.skip };
+            }
+        }
+
+        loop {
+            if self.frames_left == 0 {
+                return Asn1DecodeStatus::Done(self.result.clone());
+            }
+
+            if let Some(0) = self.stack.last().unwrap().remaining {
+                self.stack.pop();
+                if self.stack.is_empty() {
+                    return Asn1DecodeStatus::Done(self.result.clone());
+                }
+                continue;
+            }
+
+            if self.buffer.is_empty() {
+                return Asn1DecodeStatus::Done(self.result.clone());
+            }
+
+            let scope_remaining = self.stack.last().unwrap().remaining;
+            let (consumed, node) = match try_parse_tlv(&self.buffer) {
+                Ok((rest, node)) => (self.buffer.len() - rest.len(), node),
+                Err(Asn1TlvError::Incomplete(needed)) => {
+                    if self.max_buffered_bytes > 0
+                        && self.buffer.len().saturating_add(needed) > self.max_buffered_bytes
+                    {
+                        return Asn1DecodeStatus::TooLarge;
+                    }
+                    return Asn1DecodeStatus::Incomplete { needed };
+                }
+                Err(Asn1TlvError::Invalid) => {
+                    return Asn1DecodeStatus::Done(self.result.clone());
+                }
+            };
+
+            if let Some(bound) = scope_remaining {
+                if consumed > bound {
+                    // Child claims to run past its parent's declared
+                    // length; malformed nesting.
+                    return Asn1DecodeStatus::Done(self.result.clone());
+                }
+            }
+
+            let node_offset = self.total_consumed;
+            evaluate_node(&node, node_offset, &self.data, &mut self.result);
+            self.frames_left -= 1;
+
+            // Drop the header always; for a primitive node drop its value
+            // too since it has already been evaluated in full. A
+            // constructed value's bytes stay buffered to be walked as a
+            // new, nested frame.
+            let drain_now = if node.constructed { node.header_len } else { node.header_len + node.length };
+            let entered_length = node.length;
+            let constructed = node.constructed;
+
+            self.buffer.drain(..drain_now);
+            self.total_consumed += drain_now;
+            if let Some(bound) = &mut self.stack.last_mut().unwrap().remaining {
+                // The parent's declared length covers the whole child
+                // (header + value), even though a constructed child only
+                // has its header drained here -- its value length still
+                // has to come out of the parent's budget now, since the
+                // child's own frame tracks consumption of its *own*
+                // children, not how much of the parent it occupies.
+                *bound -= consumed;
+            }
+
+            if constructed {
+                self.stack.push(Asn1Frame { remaining: Some(entered_length) });
+            }
+        }
+    }
+}
+
+/// Create a new incremental decode state for `data`. `max_buffered_bytes`
+/// caps the bytes already buffered plus those an `Incomplete` status would
+/// ask for; past it the decode is abandoned as `TooLarge`. 0 means no cap.
+///
+/// # Safety
+///
+/// `data` must point to a valid `DetectAsn1Data`.
+#[no_mangle]
+pub unsafe extern "C" fn rs_asn1_decode_state_new(
+    data: *const DetectAsn1Data, max_buffered_bytes: u32,
+) -> *mut Asn1DecodeState {
+    if data.is_null() {
+        return std::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(Asn1DecodeState::new(&*data, max_buffered_bytes as usize)))
+}
+
+/// Free a state allocated by `rs_asn1_decode_state_new`.
+///
+/// # Safety
+///
+/// `ptr` must be a pointer from `rs_asn1_decode_state_new`, or null.
+#[no_mangle]
+pub unsafe extern "C" fn rs_asn1_decode_state_free(ptr: *mut Asn1DecodeState) {
+    if ptr.is_null() {
+        return;
+    }
+    drop(Box::from_raw(ptr));
+}
+
+/// Feed `new_chunk` into `state` and report how decoding stands: 0 = no
+/// match (done), 1 = match (done), 2 = incomplete, more data needed, 3 =
+/// abandoned, the in-progress node exceeded the buffering cap.
+///
+/// # Safety
+///
+/// `state` must be a valid pointer from `rs_asn1_decode_state_new`.
+/// `new_chunk` must be valid for `len` bytes (or `len` zero).
+#[no_mangle]
+pub unsafe extern "C" fn rs_asn1_decode_resume(
+    state: *mut Asn1DecodeState, new_chunk: *const u8, len: u32,
+) -> u8 {
+    if state.is_null() || (new_chunk.is_null() && len != 0) {
+        return 0;
+    }
+    let state = &mut *state;
+    let chunk = if len == 0 { &[] } else { std::slice::from_raw_parts(new_chunk, len as usize) };
+
+    match state.feed(chunk) {
+        Asn1DecodeStatus::Done(result) => result.matched as u8,
+        Asn1DecodeStatus::Incomplete { .. } => 2,
+        Asn1DecodeStatus::TooLarge => 3,
+    }
+}
+
+/// How many bytes of a primitive value's contents to show in an
+/// [`asn1_dump`] hex/ASCII preview.
+const ASN1_DUMP_PREVIEW_LEN: usize = 16;
+
+/// A friendly name for the handful of universal tags rule authors run
+/// into most often; anything else is shown as a bare tag number.
+fn universal_tag_name(tag_number: u32) -> Option<&'static str> {
+    Some(match tag_number {
+        1 => "BOOLEAN",
+        2 => "INTEGER",
+        3 => "BIT STRING",
+        4 => "OCTET STRING",
+        5 => "NULL",
+        6 => "OBJECT IDENTIFIER",
+        9 => "REAL",
+        10 => "ENUMERATED",
+        12 => "UTF8String",
+        16 => "SEQUENCE",
+        17 => "SET",
+        19 => "PrintableString",
+        22 => "IA5String",
+        23 => "UTCTime",
+        24 => "GeneralizedTime",
+        _ => return None,
+    })
+}
+
+/// Render up to `ASN1_DUMP_PREVIEW_LEN` bytes of a primitive value as
+/// `openssl asn1parse`-style hex next to an ASCII rendering.
+fn format_preview(value: &[u8]) -> String {
+    let shown = &value[..value.len().min(ASN1_DUMP_PREVIEW_LEN)];
+    let hex: Vec<String> = shown.iter().map(|b| format!("{:02x}", b)).collect();
+    let ascii: String = shown
+        .iter()
+        .map(|&b| if (0x20..0x7f).contains(&b) { b as char } else { '.' })
+        .collect();
+    let ellipsis = if value.len() > shown.len() { "..." } else { "" };
+    format!("{}{}  |{}|", hex.join(" "), ellipsis, ascii)
+}
+
+/// Descends into constructed values via an explicit stack rather than
+/// native recursion, so a deeply (as opposed to merely widely) nested
+/// structure can't exhaust the thread stack. Each stack frame carries its
+/// own indent depth, so a level that runs out of budget or hits a bad TLV
+/// reports that at its own indent before the dump goes on with the
+/// remaining siblings of its ancestors.
+fn dump_nodes(buf: &[u8], offset: usize, depth: usize, frames_left: &mut u16, out: &mut String) {
+    struct Frame<'a> {
+        buf: &'a [u8],
+        offset: usize,
+        depth: usize,
+    }
+
+    let mut stack = vec![Frame { buf, offset, depth }];
+
+    while let Some(Frame { mut buf, mut offset, depth }) = stack.pop() {
+        let indent = "  ".repeat(depth);
+
+        while !buf.is_empty() {
+            if *frames_left == 0 {
+                out.push_str(&indent);
+                out.push_str("... (max_frames reached, dump truncated)\n");
+                break;
+            }
+            *frames_left -= 1;
+
+            let node = match try_parse_tlv(buf) {
+                Ok((_, node)) => node,
+                Err(Asn1TlvError::Incomplete(needed)) => {
+                    out.push_str(&format!("{indent}... (truncated, {needed} more byte(s) needed)\n"));
+                    break;
+                }
+                Err(Asn1TlvError::Invalid) => {
+                    out.push_str(&indent);
+                    out.push_str("... (malformed TLV)\n");
+                    break;
+                }
+            };
+
+            let name = if node.class == Asn1Class::Universal {
+                universal_tag_name(node.tag_number).map(|n| format!(" {n}")).unwrap_or_default()
+            } else {
+                String::new()
+            };
+            out.push_str(&format!(
+                "{indent}[offset {offset}] {:?} tag={}{name}{} len={}",
+                node.class,
+                node.tag_number,
+                if node.constructed { " (constructed)" } else { "" },
+                node.length
+            ));
+
+            let consumed = node.header_len + node.length;
+            let next_offset = offset + consumed;
+            let rest = &buf[consumed..];
+
+            if node.constructed {
+                out.push('\n');
+                // Resume this sibling level once the child subtree is done.
+                stack.push(Frame { buf: rest, offset: next_offset, depth });
+                stack.push(Frame { buf: node.value, offset: offset + node.header_len, depth: depth + 1 });
+                break;
+            }
+
+            out.push_str("  ");
+            out.push_str(&format_preview(node.value));
+            out.push('\n');
+
+            buf = rest;
+            offset = next_offset;
+        }
+    }
+}
+
+/// Render `input` as an indented BER/DER tree listing (class, tag number,
+/// constructed flag, length and a hex/ASCII preview of primitive
+/// contents), similar to `openssl asn1parse`, so rule authors can see
+/// which node their `absolute_offset`/`relative_offset`/`oversize_length`
+/// options will land on. Bounded by `max_frames` like [`asn1_decode`].
+pub fn asn1_dump(input: &[u8], max_frames: u16) -> String {
+    let mut out = String::new();
+    let mut frames_left = max_frames;
+    dump_nodes(input, 0, 0, &mut frames_left, &mut out);
+    out
+}
+
+/// `asn1_dump`, callable from the C side for signature development and
+/// triage tooling.
+///
+/// # Safety
+///
+/// `input` must be valid for `input_len` bytes (or `input_len` zero). The
+/// returned pointer, if non-null, must be freed with `rs_asn1_dump_free`.
+#[no_mangle]
+pub unsafe extern "C" fn rs_asn1_dump(
+    input: *const u8, input_len: u32, max_frames: u16,
+) -> *mut std::os::raw::c_char {
+    if input.is_null() && input_len != 0 {
+        return std::ptr::null_mut();
+    }
+    let buf = if input_len == 0 { &[] } else { std::slice::from_raw_parts(input, input_len as usize) };
+
+    match std::ffi::CString::new(asn1_dump(buf, max_frames)) {
+        Ok(s) => s.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Free a string returned by `rs_asn1_dump`.
+///
+/// # Safety
+///
+/// `ptr` must be a pointer from `rs_asn1_dump`, or null.
+#[no_mangle]
+pub unsafe extern "C" fn rs_asn1_dump_free(ptr: *mut std::os::raw::c_char) {
+    if ptr.is_null() {
+        return;
+    }
+    drop(std::ffi::CString::from_raw(ptr));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oversize_length_match() {
+        let data = DetectAsn1Data { oversize_length: Some(2), ..Default::default() };
+        // INTEGER, length 3.
+        let input = [0x02, 0x03, 0x01, 0x02, 0x03];
+
+        let result = asn1_decode(&input, &data);
+        assert!(result.matched);
+        assert_eq!(result.conditions[0].kind, Asn1ConditionKind::OversizeLength);
+        assert_eq!(result.conditions[0].offset, 0);
+        assert_eq!(result.conditions[0].tag_number, 2);
+    }
+
+    #[test]
+    fn test_bitstring_overflow_match() {
+        let data = DetectAsn1Data { bitstring_overflow: true, ..Default::default() };
+        // BIT STRING, 9 unused bits (invalid, > 7).
+        let input = [0x03, 0x02, 0x09, 0xff];
+
+        let result = asn1_decode(&input, &data);
+        assert!(result.matched);
+        assert_eq!(result.conditions[0].kind, Asn1ConditionKind::BitstringOverflow);
+    }
+
+    #[test]
+    fn test_bitstring_without_flag_does_not_match() {
+        let data = DetectAsn1Data::default();
+        let input = [0x03, 0x02, 0x09, 0xff];
+
+        let result = asn1_decode(&input, &data);
+        assert!(!result.matched);
+    }
+
+    #[test]
+    fn test_double_overflow_match() {
+        let data = DetectAsn1Data { double_overflow: true, ..Default::default() };
+        // REAL, binary encoding, 2-octet exponent set far past what an
+        // f64 can represent.
+        let input = [0x09, 0x04, 0x81, 0x07, 0xd0, 0x01];
+
+        let result = asn1_decode(&input, &data);
+        assert!(result.matched);
+        assert_eq!(result.conditions[0].kind, Asn1ConditionKind::DoubleOverflow);
+    }
+
+    #[test]
+    fn test_double_overflow_zero_does_not_match() {
+        let data = DetectAsn1Data { double_overflow: true, ..Default::default() };
+        // REAL, empty encoding: the value zero.
+        let input = [0x09, 0x00];
+
+        let result = asn1_decode(&input, &data);
+        assert!(!result.matched);
+    }
+
+    #[test]
+    fn test_double_overflow_catches_oversized_exponent_length() {
+        let data = DetectAsn1Data { double_overflow: true, ..Default::default() };
+        // REAL, binary encoding, long-form exponent length of 255 octets
+        // (format octet 0x83): a genuinely astronomical exponent (leading
+        // byte 0x7f, zero-padded) must not be truncated down to something
+        // an f64 can hold just because it doesn't fit a fixed-width
+        // accumulator.
+        let mut value = vec![0x83u8, 0xff, 0x7f];
+        value.extend(std::iter::repeat_n(0u8, 254));
+        value.push(0x01); // mantissa
+        assert_eq!(value.len(), 258);
+
+        let mut input = vec![0x09, 0x82, 0x01, 0x02]; // REAL, long-form length 258
+        input.extend(value);
+
+        let result = asn1_decode(&input, &data);
+        assert!(result.matched);
+        assert_eq!(result.conditions[0].kind, Asn1ConditionKind::DoubleOverflow);
+    }
+
+    #[test]
+    fn test_double_overflow_accepts_comma_decimal_separator() {
+        let data = DetectAsn1Data { double_overflow: true, ..Default::default() };
+        // REAL, decimal encoding (format octet 0x02 = ISO 6093 NR2), using
+        // the comma decimal separator NR2/NR3 explicitly permit.
+        let input = [0x09, 0x05, 0x02, b'3', b',', b'1', b'4'];
+
+        let result = asn1_decode(&input, &data);
+        assert!(!result.matched);
+    }
+
+    #[test]
+    fn test_double_overflow_does_not_panic_on_i64_min_exponent() {
+        let data = DetectAsn1Data { double_overflow: true, ..Default::default() };
+        // REAL, binary encoding, 8-octet exponent that decodes to exactly
+        // `i64::MIN`, which `.abs()` cannot represent as a positive `i64`.
+        let value = [0x83, 0x08, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+        let mut input = vec![0x09, value.len() as u8];
+        input.extend_from_slice(&value);
+
+        let result = asn1_decode(&input, &data);
+        assert!(result.matched);
+        assert_eq!(result.conditions[0].kind, Asn1ConditionKind::DoubleOverflow);
+    }
+
+    #[test]
+    fn test_absolute_offset_positions_cursor() {
+        let data =
+            DetectAsn1Data { oversize_length: Some(0), absolute_offset: Some(2), ..Default::default() };
+        let input = [0xaa, 0xaa, 0x02, 0x01, 0x05];
+
+        let result = asn1_decode(&input, &data);
+        assert!(result.matched);
+        assert_eq!(result.conditions[0].offset, 2);
+    }
+
+    #[test]
+    fn test_relative_offset_is_added_to_absolute_offset() {
+        let data = DetectAsn1Data {
+            oversize_length: Some(0),
+            absolute_offset: Some(1),
+            relative_offset: Some(1),
+            ..Default::default()
+        };
+        let input = [0xaa, 0xaa, 0x02, 0x01, 0x05];
+
+        let result = asn1_decode(&input, &data);
+        assert!(result.matched);
+        assert_eq!(result.conditions[0].offset, 2);
+    }
+
+    #[test]
+    fn test_recurses_into_constructed_nodes() {
+        let data = DetectAsn1Data { oversize_length: Some(0), ..Default::default() };
+        // SEQUENCE containing one INTEGER.
+        let input = [0x30, 0x03, 0x02, 0x01, 0x05];
+
+        let result = asn1_decode(&input, &data);
+        // Both the SEQUENCE and the INTEGER exceed an oversize_length of 0.
+        assert_eq!(result.conditions.len(), 2);
+        assert_eq!(result.conditions[0].tag_number, 16);
+        assert_eq!(result.conditions[1].tag_number, 2);
+    }
+
+    #[test]
+    fn test_max_frames_bounds_iteration() {
+        let data = DetectAsn1Data { oversize_length: Some(0), max_frames: 2, ..Default::default() };
+        // Three independent INTEGER TLVs.
+        let input = [0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x02, 0x01, 0x03];
+
+        let result = asn1_decode(&input, &data);
+        assert_eq!(result.conditions.len(), 2);
+    }
+
+    /// Builds `depth` empty SEQUENCEs nested one inside another, innermost
+    /// first, so that decoding it requires descending `depth` levels deep
+    /// (as opposed to merely visiting many sibling nodes).
+    fn nested_sequences(depth: usize) -> Vec<u8> {
+        let mut encoded: Vec<u8> = Vec::new();
+        for _ in 0..depth {
+            let mut length = Vec::new();
+            if encoded.len() < 0x80 {
+                length.push(encoded.len() as u8);
+            } else {
+                let len_bytes = encoded.len().to_be_bytes();
+                let len_bytes = &len_bytes[len_bytes.iter().position(|&b| b != 0).unwrap_or(7)..];
+                length.push(0x80 | len_bytes.len() as u8);
+                length.extend_from_slice(len_bytes);
+            }
+            let mut wrapped = vec![0x30];
+            wrapped.extend(length);
+            wrapped.extend(encoded);
+            encoded = wrapped;
+        }
+        encoded
+    }
+
+    #[test]
+    fn test_deeply_nested_input_does_not_overflow_stack() {
+        let data = DetectAsn1Data { oversize_length: Some(0), max_frames: 50_000, ..Default::default() };
+        let depth = 20_000;
+        let input = nested_sequences(depth);
+
+        // Should return without crashing the process. The innermost,
+        // empty SEQUENCE doesn't itself exceed an oversize_length of 0.
+        let result = asn1_decode(&input, &data);
+        assert_eq!(result.conditions.len(), depth - 1);
+    }
+
+    #[test]
+    fn test_deeply_nested_input_does_not_overflow_stack_in_dump() {
+        let depth = 20_000;
+        let input = nested_sequences(depth);
+
+        // Should return without crashing the process.
+        let dump = asn1_dump(&input, 50_000);
+        assert!(dump.contains("SEQUENCE"));
+    }
+
+    #[test]
+    fn test_resume_reports_incomplete_then_matches() {
+        let data = DetectAsn1Data { oversize_length: Some(2), ..Default::default() };
+        let mut state = Asn1DecodeState::new(&data, 0);
+
+        // Header plus two of the three value bytes.
+        match state.feed(&[0x02, 0x03, 0x01, 0x02]) {
+            Asn1DecodeStatus::Incomplete { needed } => assert_eq!(needed, 1),
+            other => panic!("expected Incomplete, got {:?}", other),
+        }
+
+        match state.feed(&[0x03]) {
+            Asn1DecodeStatus::Done(result) => {
+                assert!(result.matched);
+                assert_eq!(result.conditions[0].kind, Asn1ConditionKind::OversizeLength);
+            }
+            other => panic!("expected Done, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resume_decrements_parent_remaining_by_full_child_size() {
+        let data = DetectAsn1Data { oversize_length: Some(0), ..Default::default() };
+
+        // Outer SEQUENCE declares length 7, but its true content is 8
+        // bytes: a 5-byte nested SEQUENCE{OCTET STRING} followed by a
+        // 3-byte INTEGER that actually lies outside the declared length.
+        let nested_octet_string = [0x04, 0x01, 0xaa];
+        let nested_sequence = [0x30, 0x03, 0x04, 0x01, 0xaa];
+        assert_eq!(nested_sequence[2..], nested_octet_string);
+        let integer = [0x02, 0x01, 0xbb];
+        let mut input = vec![0x30, 0x07];
+        input.extend_from_slice(&nested_sequence);
+        input.extend_from_slice(&integer);
+
+        let mut state = Asn1DecodeState::new(&data, 0);
+        let status = state.feed(&input);
+
+        // The trailing INTEGER must not be evaluated as if it were still
+        // inside the outer SEQUENCE: only the outer SEQUENCE, the nested
+        // SEQUENCE and its OCTET STRING should have been scored.
+        match status {
+            Asn1DecodeStatus::Done(result) => {
+                assert_eq!(result.conditions.len(), 3);
+                assert!(result.conditions.iter().all(|c| c.tag_number != 2));
+            }
+            other => panic!("expected Done, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resume_honors_absolute_offset() {
+        let data =
+            DetectAsn1Data { oversize_length: Some(0), absolute_offset: Some(2), ..Default::default() };
+        let input = [0xaa, 0xaa, 0x02, 0x01, 0x05];
+
+        let mut state = Asn1DecodeState::new(&data, 0);
+        match state.feed(&input) {
+            Asn1DecodeStatus::Done(result) => {
+                assert!(result.matched);
+                assert_eq!(result.conditions[0].offset, 2);
+            }
+            other => panic!("expected Done, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resume_honors_absolute_offset_split_across_segments() {
+        let data =
+            DetectAsn1Data { oversize_length: Some(0), absolute_offset: Some(2), ..Default::default() };
+        let input = [0xaa, 0xaa, 0x02, 0x01, 0x05];
+
+        let mut state = Asn1DecodeState::new(&data, 0);
+        match state.feed(&input[..1]) {
+            Asn1DecodeStatus::Incomplete { needed } => assert_eq!(needed, 1),
+            other => panic!("expected Incomplete, got {:?}", other),
+        }
+        match state.feed(&input[1..]) {
+            Asn1DecodeStatus::Done(result) => {
+                assert!(result.matched);
+                assert_eq!(result.conditions[0].offset, 2);
+            }
+            other => panic!("expected Done, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resume_respects_max_buffered_bytes() {
+        let data = DetectAsn1Data::default();
+        // Declares a 100-byte value but only the header is buffered.
+        let mut state = Asn1DecodeState::new(&data, 8);
+
+        let status = state.feed(&[0x02, 0x64]);
+        assert_eq!(status, Asn1DecodeStatus::TooLarge);
+    }
+
+    #[test]
+    fn test_dump_renders_nested_tree() {
+        // SEQUENCE containing one INTEGER.
+        let input = [0x30, 0x03, 0x02, 0x01, 0x05];
+
+        let dump = asn1_dump(&input, 30);
+        let expected = "[offset 0] Universal tag=16 SEQUENCE (constructed) len=3\n".to_string()
+            + "  [offset 2] Universal tag=2 INTEGER len=1  05  |.|\n";
+        assert_eq!(dump, expected);
+    }
+
+    #[test]
+    fn test_dump_bounds_by_max_frames() {
+        let input = [0x30, 0x03, 0x02, 0x01, 0x05];
+
+        let dump = asn1_dump(&input, 1);
+        assert!(dump.contains("max_frames reached"));
+    }
+
+    #[test]
+    fn test_dump_reports_incomplete_tlv() {
+        let input = [0x02, 0x05, 0x01, 0x02];
+
+        let dump = asn1_dump(&input, 30);
+        assert!(dump.contains("truncated"));
+    }
+}
@@ -22,11 +22,62 @@ use nom::character::complete::{digit1, multispace0, multispace1};
 use nom::combinator::{map_res, opt};
 use nom::sequence::{separated_pair, tuple};
 use nom::IResult;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 
 const ASN1_DEFAULT_MAX_FRAMES: u16 = 30;
 
+/// The option keywords `asn1_parse_rule` knows how to parse, in the order
+/// they are tried; also doubles as the "expected one of" list reported by
+/// [`Asn1ParseError`].
+const ASN1_OPTION_KEYWORDS: &[&str] = &[
+    "bitstring_overflow",
+    "double_overflow",
+    "oversize_length",
+    "absolute_offset",
+    "relative_offset",
+];
+
+/// Error returned by `asn1_parse_rule` when it cannot make sense of the
+/// rest of the keyword's argument, carrying enough context for the C
+/// signature loader to point at the offending character.
+#[derive(Debug, PartialEq)]
+pub struct Asn1ParseError {
+    /// Byte offset into the original argument where parsing stalled.
+    pub offset: usize,
+    /// The option keywords that would have been accepted at `offset`.
+    pub expected: &'static [&'static str],
+}
+
+impl Asn1ParseError {
+    fn at(original: &str, rest: &str) -> Self {
+        Asn1ParseError { offset: original.len() - rest.len(), expected: ASN1_OPTION_KEYWORDS }
+    }
+}
+
+impl std::fmt::Display for Asn1ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown asn1 option near offset {}: expected one of {}",
+            self.offset,
+            self.expected.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for Asn1ParseError {}
+
+fn apply_max_frames_config(data: &mut DetectAsn1Data) {
+    if let Some(max_frames) = crate::conf::conf_get("asn1-max-frames") {
+        if let Ok(v) = max_frames.parse::<u16>() {
+            data.max_frames = v;
+        } else {
+            SCLogDebug!("Could not parse asn1-max-frames: {}", max_frames);
+        };
+    }
+}
+
 /// Parse the asn1 keyword and return a pointer to a `DetectAsn1Data`
 /// containing the parsed options, returns null on failure
 ///
@@ -46,25 +97,74 @@ pub unsafe extern "C" fn rs_detect_asn1_parse(input: *const c_char) -> *mut Dete
         }
     };
 
-    match asn1_parse_rule(&arg) {
+    match asn1_parse_rule(arg) {
         Ok((_rest, data)) => {
             let mut data = data;
+            apply_max_frames_config(&mut data);
+            Box::into_raw(Box::new(data))
+        }
+        Err(_) => std::ptr::null_mut(),
+    }
+}
 
-            // Get configuration value
-            if let Some(max_frames) = crate::conf::conf_get("asn1-max-frames") {
-                if let Ok(v) = max_frames.parse::<u16>() {
-                    data.max_frames = v;
-                } else {
-                    SCLogDebug!("Could not parse asn1-max-frames: {}", max_frames);
-                };
-            }
+/// Parse the asn1 keyword like `rs_detect_asn1_parse`, but when the options
+/// fail to parse fill `*err` with an owned, human-readable diagnostic naming
+/// the byte offset and the options that were expected there. For a null or
+/// non-UTF-8 `input`, null is returned and `*err` is left null.
+///
+/// # Safety
+///
+/// pointer must be free'd using `rs_detect_asn1_free`; `*err`, if set,
+/// must be free'd using `rs_detect_asn1_parse_error_free`. `err` must be a
+/// valid pointer to a `*mut c_char`.
+#[no_mangle]
+pub unsafe extern "C" fn rs_detect_asn1_parse_ex(
+    input: *const c_char, err: *mut *mut c_char,
+) -> *mut DetectAsn1Data {
+    if !err.is_null() {
+        *err = std::ptr::null_mut();
+    }
+    if input.is_null() {
+        return std::ptr::null_mut();
+    }
 
+    let arg = match CStr::from_ptr(input).to_str() {
+        Ok(arg) => arg,
+        _ => {
+            return std::ptr::null_mut();
+        }
+    };
+
+    match asn1_parse_rule(arg) {
+        Ok((_rest, data)) => {
+            let mut data = data;
+            apply_max_frames_config(&mut data);
             Box::into_raw(Box::new(data))
         }
-        Err(_) => std::ptr::null_mut(),
+        Err(e) => {
+            if !err.is_null() {
+                if let Ok(msg) = CString::new(e.to_string()) {
+                    *err = msg.into_raw();
+                }
+            }
+            std::ptr::null_mut()
+        }
     }
 }
 
+/// Free an error message allocated by `rs_detect_asn1_parse_ex`
+///
+/// # Safety
+///
+/// ptr must be a pointer obtained from `rs_detect_asn1_parse_ex`, or null
+#[no_mangle]
+pub unsafe extern "C" fn rs_detect_asn1_parse_error_free(ptr: *mut c_char) {
+    if ptr.is_null() {
+        return;
+    }
+    drop(CString::from_raw(ptr));
+}
+
 /// Free a `DetectAsn1Data` object allocated by Rust
 ///
 /// # Safety
@@ -79,7 +179,7 @@ pub unsafe extern "C" fn rs_detect_asn1_free(ptr: *mut DetectAsn1Data) {
 }
 
 /// Struct to hold parsed asn1 keyword options
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DetectAsn1Data {
     pub bitstring_overflow: bool,
     pub double_overflow: bool,
@@ -113,13 +213,10 @@ fn parse_i32_number(input: &str) -> IResult<&str, i32> {
 }
 
 /// Parse asn1 keyword options
-pub(super) fn asn1_parse_rule(input: &str) -> IResult<&str, DetectAsn1Data> {
+pub(super) fn asn1_parse_rule(input: &str) -> Result<(&str, DetectAsn1Data), Asn1ParseError> {
     // If nothing to parse, return
     if input.is_empty() {
-        return Err(nom::Err::Error(nom::error::make_error(
-            input,
-            nom::error::ErrorKind::Eof,
-        )));
+        return Err(Asn1ParseError::at(input, input));
     }
 
     // Rule parsing functions
@@ -168,7 +265,8 @@ pub(super) fn asn1_parse_rule(input: &str) -> IResult<&str, DetectAsn1Data> {
             opt(absolute_offset),
             opt(relative_offset),
             opt(alt((multispace1, tag(",")))),
-        ))(rest)?;
+        ))(rest)
+        .expect("asn1 option alternatives are all optional and cannot fail");
 
         if bitstring_overflow.is_some() {
             data.bitstring_overflow = true;
@@ -181,10 +279,7 @@ pub(super) fn asn1_parse_rule(input: &str) -> IResult<&str, DetectAsn1Data> {
         } else if let Some((_, v)) = relative_offset {
             data.relative_offset = Some(v);
         } else {
-            return Err(nom::Err::Error(nom::error::make_error(
-                rest,
-                nom::error::ErrorKind::Verify,
-            )));
+            return Err(Asn1ParseError::at(input, rest));
         }
 
         rest = new_rest;
@@ -203,21 +298,21 @@ mod tests {
         DetectAsn1Data { oversize_length: Some(1024), ..Default::default()};
         "check that we parse oversize_length correctly")]
     #[test_case("oversize_length",
-        DetectAsn1Data::default() => panics "Error((\"oversize_length\", Verify))";
+        DetectAsn1Data::default() => panics "Asn1ParseError { offset: 0,";
         "check that we fail if the needed arg oversize_length is not given")]
     // Test absolute_offset
     #[test_case("absolute_offset 1024",
         DetectAsn1Data { absolute_offset: Some(1024), ..Default::default()};
         "check that we parse absolute_offset correctly")]
     #[test_case("absolute_offset",
-        DetectAsn1Data::default() => panics "Error((\"absolute_offset\", Verify))";
+        DetectAsn1Data::default() => panics "Asn1ParseError { offset: 0,";
         "check that we fail if the needed arg absolute_offset is not given")]
     // Test relative_offset
     #[test_case("relative_offset 1024",
         DetectAsn1Data { relative_offset: Some(1024), ..Default::default()};
         "check that we parse relative_offset correctly")]
     #[test_case("relative_offset",
-        DetectAsn1Data::default() => panics "Error((\"relative_offset\", Verify))";
+        DetectAsn1Data::default() => panics "Asn1ParseError { offset: 0,";
         "check that we fail if the needed arg relative_offset is not given")]
     // Test bitstring_overflow
     #[test_case("bitstring_overflow",
@@ -254,17 +349,17 @@ mod tests {
         "2. check for combinations of keywords (space/comma/newline seperated)")]
     // Test empty
     #[test_case("",
-        DetectAsn1Data::default() => panics "Error((\"\", Eof))";
+        DetectAsn1Data::default() => panics "Asn1ParseError { offset: 0,";
         "test that we break with a empty string")]
     // Test invalid rules
     #[test_case("oversize_length 1024, some_other_param 360",
-        DetectAsn1Data::default() => panics "Error((\" some_other_param 360\", Verify))";
+        DetectAsn1Data::default() => panics "Asn1ParseError { offset: 21,";
         "test that we break on invalid options")]
     #[test_case("oversize_length 1024,,",
-        DetectAsn1Data::default() => panics "Error((\",\", Verify))";
+        DetectAsn1Data::default() => panics "Asn1ParseError { offset: 21,";
         "test that we break on invalid format (missing option)")]
     #[test_case("bitstring_overflowabsolute_offset",
-        DetectAsn1Data::default() => panics "Error((\"absolute_offset\", Verify))";
+        DetectAsn1Data::default() => panics "Asn1ParseError { offset: 18,";
         "test that we break on invalid format (missing seperator)")]
     fn test_asn1_parse_rule(input: &str, expected: DetectAsn1Data) {
         let (rest, res) = asn1_parse_rule(input).unwrap();
@@ -272,4 +367,15 @@ mod tests {
         assert_eq!(0, rest.len());
         assert_eq!(expected, res);
     }
+
+    #[test]
+    fn test_asn1_parse_error_message() {
+        let err = asn1_parse_rule("oversize_length 1024, some_other_param 360").unwrap_err();
+        assert_eq!(
+            "unknown asn1 option near offset 21: expected one of \
+             bitstring_overflow, double_overflow, oversize_length, \
+             absolute_offset, relative_offset",
+            err.to_string()
+        );
+    }
 }
\ No newline at end of file